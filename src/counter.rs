@@ -20,5 +20,9 @@ pub trait Counter {
         + IntoU128;
     type ValueType: Debug + Copy + Clone + Sub<Self::ValueType, Output = Self::DifferenceType>;
 
+    /// Unit the counter's values are expressed in, e.g. for the `events:`
+    /// header of a Callgrind export.
+    const UNIT: &'static str;
+
     fn read(&self) -> Self::ValueType;
 }
@@ -0,0 +1,221 @@
+#![allow(dead_code)]
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    convert::TryInto,
+    fs::File,
+    io::{self, BufWriter, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use string_interner::Symbol as _;
+
+use crate::{
+    profiler::{StringInterner, Symbol},
+    FunctionStatistics,
+};
+
+/// Tags the kind of event recorded in the streaming log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum EventKind {
+    Call = 0,
+    Return = 1,
+    CCall = 2,
+    CReturn = 3,
+}
+
+impl EventKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(EventKind::Call),
+            1 => Some(EventKind::Return),
+            2 => Some(EventKind::CCall),
+            3 => Some(EventKind::CReturn),
+            _ => None,
+        }
+    }
+}
+
+/// Byte used to mark the end of the binary event section, ahead of the
+/// trailing string table.
+const END_OF_EVENTS: u8 = 0xFF;
+
+/// `kind` (1) + `symbol` (4) + `counter_value` (16) + `thread_id` (8).
+const EVENT_SIZE: usize = 1 + 4 + 16 + 8;
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static THREAD_ID: Cell<u64> = Cell::new(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+}
+
+fn current_thread_id() -> u64 {
+    THREAD_ID.with(Cell::get)
+}
+
+/// Opt-in streaming backend that appends compact, fixed-width binary
+/// events straight to disk instead of growing a `SamplesMap`, so it stays
+/// cheap on the hot `profiler_callback` path (no allocation, no hashing).
+///
+/// The string table mapping symbols back to names is only resolved once,
+/// when the log is [`finish`](EventLog::finish)ed at shutdown.
+pub struct EventLog {
+    writer: BufWriter<File>,
+}
+
+impl EventLog {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    #[inline]
+    fn write_event(&mut self, kind: EventKind, symbol: Symbol, counter_value: u128) -> io::Result<()> {
+        let mut event = [0u8; EVENT_SIZE];
+        event[0] = kind as u8;
+        event[1..5].copy_from_slice(&(symbol.to_usize() as u32).to_le_bytes());
+        event[5..21].copy_from_slice(&counter_value.to_le_bytes());
+        event[21..29].copy_from_slice(&current_thread_id().to_le_bytes());
+        self.writer.write_all(&event)
+    }
+
+    #[inline]
+    pub fn record_call(&mut self, symbol: Symbol, counter_value: u128) -> io::Result<()> {
+        self.write_event(EventKind::Call, symbol, counter_value)
+    }
+
+    #[inline]
+    pub fn record_return(&mut self, symbol: Symbol, counter_value: u128) -> io::Result<()> {
+        self.write_event(EventKind::Return, symbol, counter_value)
+    }
+
+    #[inline]
+    pub fn record_c_call(&mut self, symbol: Symbol, counter_value: u128) -> io::Result<()> {
+        self.write_event(EventKind::CCall, symbol, counter_value)
+    }
+
+    #[inline]
+    pub fn record_c_return(&mut self, symbol: Symbol, counter_value: u128) -> io::Result<()> {
+        self.write_event(EventKind::CReturn, symbol, counter_value)
+    }
+
+    /// Ends the event section with a marker, appends the string table built
+    /// up by `interner`, then flushes everything still buffered. Call once,
+    /// at profiler shutdown.
+    pub fn finish(mut self, interner: &StringInterner) -> io::Result<()> {
+        self.writer.write_all(&[END_OF_EVENTS])?;
+
+        for (symbol, name) in interner.iter() {
+            writeln!(self.writer, "{} {}", symbol.to_usize(), name)?;
+        }
+
+        self.writer.flush()
+    }
+}
+
+/// One decoded record from the event stream.
+struct RawEvent {
+    kind: EventKind,
+    symbol: u32,
+    counter_value: u128,
+    thread_id: u64,
+}
+
+/// A single stack frame being timed while replaying the event stream.
+struct Frame {
+    symbol: u32,
+    start: u128,
+    child_cost: u128,
+}
+
+/// Reads an [`EventLog`] back, pairing call/return events per thread to
+/// reconstruct each function's statistics without needing the interpreter.
+pub struct EventLogReader;
+
+impl EventLogReader {
+    /// Decodes every event in `path` and reduces them into
+    /// [`FunctionStatistics`], keyed by symbol.
+    pub fn read_statistics(path: &str) -> io::Result<Vec<FunctionStatistics>> {
+        let bytes = std::fs::read(path)?;
+
+        let mut offset = 0;
+        let mut events = Vec::new();
+        while offset < bytes.len() && bytes[offset] != END_OF_EVENTS {
+            let chunk = &bytes[offset..offset + EVENT_SIZE];
+            let kind = EventKind::from_u8(chunk[0]).expect("corrupt event log");
+            let symbol = u32::from_le_bytes(chunk[1..5].try_into().unwrap());
+            let counter_value = u128::from_le_bytes(chunk[5..21].try_into().unwrap());
+            let thread_id = u64::from_le_bytes(chunk[21..29].try_into().unwrap());
+            events.push(RawEvent {
+                kind,
+                symbol,
+                counter_value,
+                thread_id,
+            });
+            offset += EVENT_SIZE;
+        }
+
+        let names: HashMap<u32, String> = if offset < bytes.len() {
+            let text = std::str::from_utf8(&bytes[offset + 1..]).unwrap_or_default();
+            text.lines()
+                .filter_map(|line| {
+                    let mut parts = line.splitn(2, ' ');
+                    let symbol: u32 = parts.next()?.parse().ok()?;
+                    let name = parts.next()?.to_owned();
+                    Some((symbol, name))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        // Pair call/return events per thread using a stack, the same way
+        // `Stopwatch` accumulates exclusive vs. inclusive time while live.
+        let mut stacks: HashMap<u64, Vec<Frame>> = HashMap::new();
+        let mut totals: HashMap<u32, (usize, u128, u128)> = HashMap::new();
+
+        for event in events {
+            let stack = stacks.entry(event.thread_id).or_default();
+            match event.kind {
+                EventKind::Call | EventKind::CCall => {
+                    stack.push(Frame {
+                        symbol: event.symbol,
+                        start: event.counter_value,
+                        child_cost: 0,
+                    });
+                }
+                EventKind::Return | EventKind::CReturn => {
+                    if let Some(frame) = stack.pop() {
+                        let cumulative = event.counter_value - frame.start;
+                        let total = cumulative - frame.child_cost;
+
+                        if let Some(parent) = stack.last_mut() {
+                            parent.child_cost += cumulative;
+                        }
+
+                        let entry = totals.entry(frame.symbol).or_insert((0, 0, 0));
+                        entry.0 += 1;
+                        entry.1 += total;
+                        entry.2 += cumulative;
+                    }
+                }
+            }
+        }
+
+        Ok(totals
+            .into_iter()
+            .map(|(symbol, (num_calls, total, cumulative))| FunctionStatistics {
+                name: names
+                    .get(&symbol)
+                    .cloned()
+                    .unwrap_or_else(|| symbol.to_string()),
+                num_calls,
+                total,
+                cumulative,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,121 @@
+use std::env;
+
+/// Controls which functions get profiled and how deep/long a call must be
+/// to be kept, parsed from an environment variable spec (modeled on
+/// rust-analyzer's `Filter::from_spec`).
+///
+/// Spec syntax:
+/// - `*` profiles everything
+/// - `foo|bar|baz` only profiles the named functions
+/// - an optional `@N` suffix bounds the maximum nesting depth recorded
+/// - an optional `>T` suffix (e.g. `>10ms`) discards samples whose
+///   cumulative duration falls under the threshold
+#[derive(Debug, Clone)]
+pub struct Filter {
+    allow_all: bool,
+    allowed: Vec<String>,
+    max_depth: Option<usize>,
+    min_cumulative_ns: Option<u128>,
+}
+
+impl Default for Filter {
+    /// Profiles everything, at any depth, regardless of duration.
+    fn default() -> Self {
+        Self {
+            allow_all: true,
+            allowed: Vec::new(),
+            max_depth: None,
+            min_cumulative_ns: None,
+        }
+    }
+}
+
+impl Filter {
+    /// Reads and parses the filter spec from `var`, falling back to
+    /// profiling everything if it is unset or empty.
+    pub fn from_env(var: &str) -> Self {
+        match env::var(var) {
+            Ok(spec) => Self::from_spec(&spec),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Parses a filter spec such as `foo|bar@4>10ms`.
+    pub fn from_spec(spec: &str) -> Self {
+        let mut spec = spec.trim();
+
+        let mut min_cumulative_ns = None;
+        if let Some(idx) = spec.find('>') {
+            min_cumulative_ns = parse_duration_ns(&spec[idx + 1..]);
+            spec = &spec[..idx];
+        }
+
+        let mut max_depth = None;
+        if let Some(idx) = spec.find('@') {
+            max_depth = spec[idx + 1..].parse().ok();
+            spec = &spec[..idx];
+        }
+
+        if spec == "*" || spec.is_empty() {
+            return Self {
+                allow_all: true,
+                allowed: Vec::new(),
+                max_depth,
+                min_cumulative_ns,
+            };
+        }
+
+        let allowed = spec.split('|').map(str::to_owned).collect();
+        Self {
+            allow_all: false,
+            allowed,
+            max_depth,
+            min_cumulative_ns,
+        }
+    }
+
+    /// Returns whether a function with the given name should start being
+    /// profiled when entered at the given nesting depth.
+    pub fn allows(&self, name: &str, depth: usize) -> bool {
+        if let Some(max_depth) = self.max_depth {
+            if depth > max_depth {
+                return false;
+            }
+        }
+
+        self.allow_all || self.allowed.iter().any(|allowed| allowed == name)
+    }
+
+    /// Returns whether a finished sample should be kept, given its
+    /// cumulative duration in nanoseconds.
+    ///
+    /// The `>T` spec is always parsed as wall-clock time, so this is only
+    /// meaningful for samples actually measured in nanoseconds — callers
+    /// profiling with a non-time [`Counter`](crate::counter::Counter) (e.g.
+    /// events or bytes) need to gate calling this on the counter's own
+    /// `UNIT` rather than passing a count through as if it were `cumulative_ns`.
+    pub fn meets_threshold(&self, cumulative_ns: u128) -> bool {
+        match self.min_cumulative_ns {
+            Some(threshold) => cumulative_ns >= threshold,
+            None => true,
+        }
+    }
+}
+
+/// Parses a duration spec such as `10ms` or `500us` into nanoseconds.
+fn parse_duration_ns(spec: &str) -> Option<u128> {
+    let spec = spec.trim();
+    let split_at = spec.find(|c: char| !c.is_ascii_digit())?;
+    let (value, unit) = spec.split_at(split_at);
+    let value: u128 = value.parse().ok()?;
+
+    let ns = match unit {
+        "ns" => value,
+        "us" | "µs" => value * 1_000,
+        "ms" => value * 1_000_000,
+        "s" => value * 1_000_000_000,
+        _ => return None,
+    };
+
+    Some(ns)
+}
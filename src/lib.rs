@@ -11,11 +11,23 @@ mod stopwatch;
 #[cfg(feature = "perfcnt")]
 mod perfcnt;
 
+#[cfg(feature = "jemalloc")]
+mod memory;
+
 mod time;
 
+mod filter;
+
+mod event_log;
+
 mod profiler;
 use crate::profiler::{AbstractProfiler, Profiler};
 
+mod tree;
+
+mod sampling;
+use crate::sampling::SamplingProfiler;
+
 thread_local! {
     static PROFILER: RefCell<Option<Box<dyn AbstractProfiler>>> = RefCell::new(None);
 }
@@ -53,37 +65,74 @@ impl PyObjectProtocol for FunctionStatistics {
     }
 }
 
+#[pyclass]
+pub struct CallTreeStats {
+    #[pyo3(get, set)]
+    name: String,
+    #[pyo3(get, set)]
+    total: u128,
+    #[pyo3(get, set)]
+    cumulative: u128,
+    #[pyo3(get, set)]
+    children: Vec<usize>,
+}
+
+#[pyproto]
+impl PyObjectProtocol for CallTreeStats {
+    fn __repr__(&self) -> String {
+        format!(
+            "{} ({} children): {} total / {} cumulative",
+            self.name,
+            self.children.len(),
+            self.total,
+            self.cumulative
+        )
+    }
+}
+
 /// An adaptive Python profiler, implemented in Rust.
 #[pyclass(unsendable)]
 pub struct AdaptiveProfiler {}
 
 #[pymethods]
 impl AdaptiveProfiler {
+    /// `mode` selects between the default deterministic instrumentation
+    /// (`"deterministic"`, via `PyEval_SetProfile`) and statistical
+    /// sampling (`"sampling"`, via a `SIGPROF` interval timer at
+    /// `frequency` Hz).
+    ///
+    /// `event_log`, if given, opts the deterministic mode into streaming
+    /// events straight to that path instead of accumulating them in memory
+    /// (see [`Profiler::enable_event_log`]); it has no effect in sampling
+    /// mode, which has no per-call events to stream.
     #[new]
-    fn new() -> Self {
-        let counter = crate::time::TimeCounter;
-        //let counter = crate::perfcnt::HardwarePerformanceCounter::cache_misses();
-        let profiler = Profiler::new(counter);
-        PROFILER.with(|p| p.replace(Some(Box::new(profiler))));
-        Self {}
+    #[args(mode = "\"deterministic\"", frequency = "100", event_log = "None")]
+    fn new(mode: &str, frequency: u32, event_log: Option<&str>) -> PyResult<Self> {
+        let profiler: Box<dyn AbstractProfiler> = match mode {
+            "sampling" => Box::new(SamplingProfiler::new(frequency)),
+            _ => {
+                let counter = crate::time::TimeCounter;
+                //let counter = crate::perfcnt::HardwarePerformanceCounter::cache_misses();
+                //let counter = crate::memory::MemoryCounter::new();
+                let mut profiler = Profiler::new(counter);
+                if let Some(path) = event_log {
+                    profiler.enable_event_log(path)?;
+                }
+                Box::new(profiler)
+            }
+        };
+
+        PROFILER.with(|p| p.replace(Some(profiler)));
+        Ok(Self {})
     }
 
     /// Starts the profiler for subsequent code.
     fn enable(&self) {
-        with_profiler(|profiler| {
-            profiler.enable();
-            unsafe {
-                let profiler_callback = profiler_callback as *const ();
-                let profiler_callback = mem::transmute(profiler_callback);
-                ffi::PyEval_SetProfile(profiler_callback, ffi::Py_None());
-            }
-        });
+        with_profiler(|profiler| profiler.enable());
     }
 
     /// Disables the monitoring of further calls.
     fn disable(&self) {
-        disable_profiling_hook();
-
         with_profiler(|profiler| profiler.disable());
     }
 
@@ -96,6 +145,20 @@ impl AdaptiveProfiler {
     fn get_statistics(&mut self) -> Vec<FunctionStatistics> {
         with_profiler(|profiler| profiler.get_statistics())
     }
+
+    /// Retrieves the hierarchical call tree for the last profiling run, as
+    /// a flat arena: each entry's `children` are indices into this list.
+    fn get_call_tree(&mut self) -> Vec<CallTreeStats> {
+        with_profiler(|profiler| profiler.get_call_tree())
+    }
+
+    /// Writes the collected statistics to `path` in the Callgrind format,
+    /// so they can be opened in KCachegrind/QCachegrind. Only supported in
+    /// the default deterministic mode.
+    fn dump_callgrind(&mut self, path: &str) -> PyResult<()> {
+        with_profiler(|profiler| profiler.dump_callgrind(path))?;
+        Ok(())
+    }
 }
 
 #[pyproto]
@@ -159,6 +222,14 @@ extern "C" fn profiler_callback(
     0
 }
 
+fn install_profiling_hook() {
+    unsafe {
+        let profiler_callback = profiler_callback as *const ();
+        let profiler_callback = mem::transmute(profiler_callback);
+        ffi::PyEval_SetProfile(profiler_callback, ffi::Py_None());
+    }
+}
+
 fn disable_profiling_hook() {
     unsafe {
         #[allow(invalid_value)]
@@ -171,6 +242,7 @@ fn disable_profiling_hook() {
 #[pymodule]
 fn adaptive_profiler(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<FunctionStatistics>()?;
+    m.add_class::<CallTreeStats>()?;
     m.add_class::<AdaptiveProfiler>()?;
 
     Ok(())
@@ -0,0 +1,78 @@
+#![allow(dead_code)]
+
+use std::ops::{Add, Sub};
+
+use jemalloc_ctl::{epoch, thread};
+
+use crate::{
+    counter::{Counter, IntoU128, Zero},
+    lifecycle::Lifecycle,
+};
+
+/// A byte count, used as both the running value and the delta between two
+/// readings of [`MemoryCounter`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Bytes(u64);
+
+impl Zero for Bytes {
+    const ZERO: Self = Bytes(0);
+}
+
+impl IntoU128 for Bytes {
+    fn into_u128(self) -> u128 {
+        u128::from(self.0)
+    }
+}
+
+impl Add for Bytes {
+    type Output = Bytes;
+
+    fn add(self, rhs: Bytes) -> Bytes {
+        Bytes(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Bytes {
+    type Output = Bytes;
+
+    fn sub(self, rhs: Bytes) -> Bytes {
+        Bytes(self.0 - rhs.0)
+    }
+}
+
+/// Counts bytes allocated by the current thread, so allocation hot spots
+/// can be attributed to Python functions the same way CPU time is.
+///
+/// Reads jemalloc's `thread.allocatedp` statistic, which hands back a
+/// pointer to a thread-local counter, so every [`Counter::read`] is a
+/// cheap dereference rather than a fresh `mallctl` call.
+pub struct MemoryCounter {
+    allocated: thread::AllocatedP,
+}
+
+impl MemoryCounter {
+    pub fn new() -> Self {
+        let allocated = thread::allocatedp::mib()
+            .and_then(|mib| mib.read())
+            .expect("jemalloc's thread.allocatedp stat is unavailable");
+
+        Self { allocated }
+    }
+}
+
+impl Lifecycle for MemoryCounter {
+    fn enable(&self) {
+        epoch::advance().expect("failed to advance the jemalloc stats epoch");
+    }
+}
+
+impl Counter for MemoryCounter {
+    type DifferenceType = Bytes;
+    type ValueType = Bytes;
+
+    const UNIT: &'static str = "Bytes";
+
+    fn read(&self) -> Self::ValueType {
+        Bytes(self.allocated.get())
+    }
+}
@@ -77,6 +77,8 @@ impl Counter for HardwarePerformanceCounter {
     type DifferenceType = u64;
     type ValueType = u64;
 
+    const UNIT: &'static str = "Events";
+
     fn read(&self) -> Self::ValueType {
         self.get()
             .read()
@@ -1,9 +1,11 @@
 use std::{
+    collections::BTreeMap,
     fs::File,
     io::{self, BufWriter, Write},
 };
 
 use splay::{SplayMap, SplaySet};
+use string_interner::Symbol as _;
 
 pub(crate) type Symbol = string_interner::symbol::SymbolU32;
 pub(crate) type StringInterner = string_interner::StringInterner<Symbol>;
@@ -13,12 +15,19 @@ pub(crate) type SamplesMap<C> = SplayMap<Symbol, Vec<Statistics<C>>>;
 
 use crate::{
     counter::{Counter, IntoU128, Zero},
+    event_log::EventLog,
+    filter::Filter,
     lifecycle::Lifecycle,
     stopwatch::{Statistics, Stopwatch},
+    tree::{CallTree, NodeId},
     update::{create_algorithm, Algorithm, UpdateAlgorithm},
-    FunctionStatistics,
+    CallTreeStats, FunctionStatistics,
 };
 
+/// Environment variable used to configure the [`Filter`] applied by every
+/// [`Profiler`].
+const FILTER_ENV_VAR: &str = "ADAPTIVE_PROFILE";
+
 pub trait AbstractProfiler: Lifecycle {
     /// Updates the function blacklist based on collected data.
     fn update(&mut self);
@@ -36,6 +45,12 @@ pub trait AbstractProfiler: Lifecycle {
     fn on_c_return(&mut self, name: &str);
 
     fn get_statistics(&mut self) -> Vec<FunctionStatistics>;
+
+    fn get_call_tree(&mut self) -> Vec<CallTreeStats>;
+
+    /// Serializes the collected statistics in the Callgrind format, so
+    /// results can be opened in KCachegrind/QCachegrind.
+    fn dump_callgrind(&self, path: &str) -> io::Result<()>;
 }
 
 /// Current profiler state.
@@ -46,10 +61,29 @@ pub struct Profiler<C: Counter + Lifecycle> {
     update_algorithm: Box<dyn UpdateAlgorithm<C>>,
     interner: StringInterner,
     blacklist: Blacklist,
+    /// Cuts profiling overhead and noise independently of the adaptive
+    /// racing heuristic, configured via [`FILTER_ENV_VAR`].
+    filter: Filter,
     stack: Vec<Stopwatch<C>>,
     samples: SamplesMap<C>,
     previous_samples: SamplesMap<C>,
     c_enter_count: Option<C::ValueType>,
+    /// Call-tree arena, fed by `on_call`/`on_return` alongside the flat
+    /// `samples` roll-up, so callers and callees stay distinguishable.
+    tree: CallTree<C>,
+    tree_stack: Vec<NodeId>,
+    /// Records, per live call, whether `on_call` skipped it (blacklisted,
+    /// or excluded by the filter). `on_return` pops this to learn exactly
+    /// what its matching `on_call` decided, instead of re-deriving it from
+    /// state — stack depth, blacklist membership — that can have changed
+    /// by the time the call returns.
+    skip_stack: Vec<bool>,
+    /// Opt-in streaming backend; when set, events are appended to disk
+    /// instead of growing `samples`. `event_log_epoch` is the counter
+    /// reading taken when the log was opened, so events can be stored as
+    /// compact deltas.
+    event_log: Option<EventLog>,
+    event_log_epoch: Option<C::ValueType>,
 }
 
 impl<C: Counter + Lifecycle> Profiler<C> {
@@ -60,13 +94,34 @@ impl<C: Counter + Lifecycle> Profiler<C> {
             update_algorithm: create_algorithm(Algorithm::Racing),
             interner: StringInterner::new(),
             blacklist: SplaySet::new(),
+            filter: Filter::from_env(FILTER_ENV_VAR),
             stack: Vec::with_capacity(1024),
             samples: SplayMap::new(),
             previous_samples: SplayMap::new(),
             c_enter_count: None,
+            tree: CallTree::new(),
+            tree_stack: Vec::with_capacity(1024),
+            skip_stack: Vec::with_capacity(1024),
+            event_log: None,
+            event_log_epoch: None,
         }
     }
 
+    /// Opts into streaming events straight to `path` instead of
+    /// accumulating them in the in-memory `samples` map.
+    pub fn enable_event_log(&mut self, path: &str) -> io::Result<()> {
+        self.event_log = Some(EventLog::create(path)?);
+        self.event_log_epoch = Some(self.counter.read());
+        Ok(())
+    }
+
+    /// Reports the counter reading as a delta from the event log's epoch,
+    /// for compact storage.
+    fn event_log_value(&self, value: C::ValueType) -> u128 {
+        let epoch = self.event_log_epoch.unwrap_or(value);
+        (value - epoch).into_u128()
+    }
+
     fn add_to_blacklist(&mut self, symbol: Symbol) {
         let current_samples = self.samples.remove(&symbol).unwrap_or_default();
 
@@ -87,6 +142,19 @@ impl<C: Counter + Lifecycle> Profiler<C> {
         self.samples.get_mut(&symbol).unwrap().push(stats);
     }
 
+    /// Applies the filter's `>T` duration threshold, which is always
+    /// expressed in wall-clock time. For a non-time counter (events,
+    /// bytes, ...) `cumulative_ns` isn't actually nanoseconds, so comparing
+    /// it against the threshold would silently discard unrelated samples —
+    /// the threshold is a no-op for those counters instead.
+    fn meets_filter_threshold(&self, cumulative_ns: u128) -> bool {
+        if C::UNIT != "Nanoseconds" {
+            return true;
+        }
+
+        self.filter.meets_threshold(cumulative_ns)
+    }
+
     #[allow(dead_code)]
     fn dump_times(&self, path: &str) -> io::Result<()> {
         // Open a file for writing
@@ -111,12 +179,29 @@ impl<C: Counter + Lifecycle> Profiler<C> {
     }
 }
 
+/// Picks a stable synthetic line number for a symbol, since the Python
+/// profiling callback only exposes `co_name`, not real source locations.
+fn synthetic_line(symbol: Symbol) -> usize {
+    symbol.to_usize() + 1
+}
+
+impl<C: Counter + Lifecycle> Drop for Profiler<C> {
+    fn drop(&mut self) {
+        if let Some(log) = self.event_log.take() {
+            log.finish(&self.interner)
+                .expect("failed to finish the event log");
+        }
+    }
+}
+
 impl<C: Counter + Lifecycle> Lifecycle for Profiler<C> {
     fn enable(&self) {
         self.counter.enable();
+        crate::install_profiling_hook();
     }
 
     fn disable(&self) {
+        crate::disable_profiling_hook();
         self.counter.disable();
     }
 }
@@ -125,7 +210,15 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
     fn on_call(&mut self, name: &str) {
         let symbol = self.interner.get_or_intern(name);
 
-        if self.blacklist.contains(&symbol) {
+        // Pushed for every call, whether or not it ends up tracked, so
+        // `on_return` can pop exactly this decision back off instead of
+        // re-deriving it from state that can change while the call is
+        // still in flight: `update()` can blacklist `symbol` mid-call, and
+        // re-checking `self.blacklist` from `on_return` would then disagree
+        // with what happened here, desyncing `stack`/`tree_stack`.
+        let skip = self.blacklist.contains(&symbol) || !self.filter.allows(name, self.stack.len());
+        self.skip_stack.push(skip);
+        if skip {
             return;
         }
 
@@ -138,12 +231,31 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
 
         // Start a new stopwatch for the function we just entered
         self.stack.push(Stopwatch::new(value));
+
+        if self.event_log.is_some() {
+            let counter_value = self.event_log_value(value);
+            self.event_log
+                .as_mut()
+                .unwrap()
+                .record_call(symbol, counter_value)
+                .expect("failed to append to the event log");
+        }
+
+        // Attach a new call-tree node as a child of the current top of stack
+        let parent = self.tree_stack.last().copied();
+        let node = self.tree.push_child(parent, symbol);
+        self.tree_stack.push(node);
     }
 
     fn on_return(&mut self, name: &str) {
         let symbol = self.interner.get_or_intern(name);
 
-        if self.blacklist.contains(&symbol) {
+        // Pop the matching `on_call`'s decision: if it skipped (whether for
+        // the blacklist or the filter), no stopwatch/tree node was
+        // pushed, so there's nothing to pop here either. Re-checking the
+        // blacklist's *current* state instead would disagree with `on_call`
+        // whenever it changed mid-call, so this always trusts the stack.
+        if self.skip_stack.pop().unwrap_or(false) {
             return;
         }
 
@@ -154,8 +266,28 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
             // Stop the associated stopwatch
             let stats = stopwatch.stop(value);
 
-            // Save the execution data
-            self.record_statistics(symbol, stats);
+            if self.event_log.is_some() {
+                let counter_value = self.event_log_value(value);
+                self.event_log
+                    .as_mut()
+                    .unwrap()
+                    .record_return(symbol, counter_value)
+                    .expect("failed to append to the event log");
+            }
+
+            // When streaming to an event log, the in-memory map is skipped
+            // (the call tree below is fed either way); otherwise fall back
+            // to the in-memory map, dropping samples
+            // that are too short-lived to be worth keeping
+            if self.event_log.is_none() && self.meets_filter_threshold(stats.cumulative.into_u128()) {
+                self.record_statistics(symbol, stats);
+            }
+
+            // Finalize the matching call-tree node, leaving it attached to
+            // its parent for later inclusive/exclusive reporting
+            if let Some(node) = self.tree_stack.pop() {
+                self.tree.finish(node, stats);
+            }
         }
 
         // If we're still have a parent function
@@ -171,7 +303,17 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
             return;
         }
 
-        self.c_enter_count = Some(self.counter.read());
+        let now = self.counter.read();
+        self.c_enter_count = Some(now);
+
+        if self.event_log.is_some() {
+            let counter_value = self.event_log_value(now);
+            self.event_log
+                .as_mut()
+                .unwrap()
+                .record_c_call(symbol, counter_value)
+                .expect("failed to append to the event log");
+        }
     }
 
     fn on_c_return(&mut self, name: &str) {
@@ -182,6 +324,16 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
         }
 
         let now = self.counter.read();
+
+        if self.event_log.is_some() {
+            let counter_value = self.event_log_value(now);
+            self.event_log
+                .as_mut()
+                .unwrap()
+                .record_c_return(symbol, counter_value)
+                .expect("failed to append to the event log");
+        }
+
         let cumulative = now - self.c_enter_count.unwrap_or(now);
         let stats = Statistics::<C> {
             total: C::DifferenceType::ZERO,
@@ -227,4 +379,91 @@ impl<C: Counter + Lifecycle> AbstractProfiler for Profiler<C> {
             })
             .collect()
     }
+
+    /// Returns the hierarchical call tree gathered so far, as a flat arena:
+    /// each entry's `children` are indices into this same vector, so the
+    /// same function recorded under different callers stays distinct.
+    fn get_call_tree(&mut self) -> Vec<CallTreeStats> {
+        self.tree
+            .nodes()
+            .iter()
+            .map(|node| {
+                let name = self.interner.resolve(node.symbol).unwrap().to_owned();
+                let (total, cumulative) = match node.stats {
+                    Some(stats) => (stats.total.into_u128(), stats.cumulative.into_u128()),
+                    // The node's function is still running (unmatched call).
+                    None => (0, 0),
+                };
+
+                CallTreeStats {
+                    name,
+                    total,
+                    cumulative,
+                    children: node.children.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Serializes the collected statistics in the Callgrind format, so
+    /// results can be opened in KCachegrind/QCachegrind.
+    ///
+    /// Since the Python profiling callback only exposes `co_name`, every
+    /// function is attributed a single synthetic line derived from its
+    /// interned [`Symbol`], rather than a real source line.
+    fn dump_callgrind(&self, path: &str) -> io::Result<()> {
+        let file = File::create(path)?;
+        let mut file = BufWriter::new(file);
+
+        writeln!(file, "events: {}", C::UNIT)?;
+        writeln!(file)?;
+
+        // Caller -> callee edges and per-symbol self-cost, both aggregated
+        // from the same pass over the live `tree` so they describe the same
+        // snapshot — sourcing self-cost from `previous_samples` instead
+        // (only populated once `get_statistics`/blacklisting rolls `samples`
+        // into it) could emit `cfn=`/`calls=` edges with no matching `fn=`
+        // block, an inconsistent Callgrind file.
+        let mut edges: BTreeMap<Symbol, BTreeMap<Symbol, (usize, u128)>> = BTreeMap::new();
+        let mut self_costs: BTreeMap<Symbol, u128> = BTreeMap::new();
+
+        for node in self.tree.nodes() {
+            let self_cost = node.stats.map(|s| s.total.into_u128()).unwrap_or(0);
+            *self_costs.entry(node.symbol).or_insert(0) += self_cost;
+
+            let parent = match node.parent {
+                Some(parent) => self.tree.nodes()[parent].symbol,
+                None => continue,
+            };
+            let inclusive = node.stats.map(|s| s.cumulative.into_u128()).unwrap_or(0);
+
+            let entry = edges.entry(parent).or_default().entry(node.symbol).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += inclusive;
+        }
+
+        for (symbol, self_cost) in &self_costs {
+            let name = self.interner.resolve(*symbol).unwrap();
+            let line = synthetic_line(*symbol);
+
+            writeln!(file, "fl=<python>")?;
+            writeln!(file, "fn={}", name)?;
+            writeln!(file, "{} {}", line, self_cost)?;
+
+            if let Some(callees) = edges.get(symbol) {
+                for (callee, (calls, inclusive)) in callees {
+                    let callee_name = self.interner.resolve(*callee).unwrap();
+                    let callee_line = synthetic_line(*callee);
+
+                    writeln!(file, "cfn={}", callee_name)?;
+                    writeln!(file, "calls={} {}", calls, callee_line)?;
+                    writeln!(file, "{} {}", line, inclusive)?;
+                }
+            }
+
+            writeln!(file)?;
+        }
+
+        Ok(())
+    }
 }
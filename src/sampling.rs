@@ -0,0 +1,368 @@
+use std::{
+    cell::UnsafeCell,
+    collections::HashSet,
+    io,
+    ptr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use pyo3::ffi;
+
+use crate::{
+    lifecycle::Lifecycle,
+    profiler::{AbstractProfiler, StringInterner, Symbol},
+    CallTreeStats, FunctionStatistics,
+};
+
+/// Maximum number of frames captured per sample. Deep recursion is
+/// truncated rather than growing the (preallocated) sample buffer.
+const MAX_STACK_DEPTH: usize = 64;
+
+/// Maximum number of bytes kept per frame name. Longer identifiers are
+/// truncated rather than allocating, since the name has to be copied out
+/// of a preallocated buffer from the signal handler.
+const MAX_NAME_LEN: usize = 64;
+
+/// Number of samples each of [`SampleRing`]'s two buffers can hold before
+/// further samples are dropped until the next [`SampleRing::drain`].
+const RING_CAPACITY: usize = 4096;
+
+/// A `co_name` copied out byte-for-byte, rather than a `*mut PyObject` or
+/// an interned [`Symbol`]: the signal handler can't safely take a
+/// reference to the Python object (see [`copy_frame_name`]) or intern a
+/// `&str` (interning allocates/hashes), so it copies raw bytes into a
+/// preallocated slot instead.
+#[derive(Clone, Copy)]
+struct FrameName {
+    len: u8,
+    bytes: [u8; MAX_NAME_LEN],
+}
+
+impl FrameName {
+    const EMPTY: Self = Self {
+        len: 0,
+        bytes: [0; MAX_NAME_LEN],
+    };
+
+    fn as_str(&self) -> Option<&str> {
+        if self.len == 0 {
+            return None;
+        }
+        std::str::from_utf8(&self.bytes[..self.len as usize]).ok()
+    }
+}
+
+/// One stack sample captured by the `SIGPROF` handler.
+#[derive(Clone, Copy)]
+struct RawSample {
+    len: usize,
+    frames: [FrameName; MAX_STACK_DEPTH],
+}
+
+impl RawSample {
+    const EMPTY: Self = Self {
+        len: 0,
+        frames: [FrameName::EMPTY; MAX_STACK_DEPTH],
+    };
+}
+
+/// A ring buffer the signal handler can push into without allocating or
+/// touching anything beyond atomics and its own preallocated storage.
+///
+/// Samples can land from any thread: `ITIMER_PROF` is a process-wide timer,
+/// and the kernel delivers `SIGPROF` to whichever thread happens to be
+/// running, not necessarily the one that will later call
+/// [`SampleRing::drain`]. So this isn't a `thread_local` — it's one shared
+/// ring, and `push` has to be safe to call concurrently from handlers
+/// firing on different threads, not just reentrantly on the same one.
+///
+/// It double-buffers so `drain` can swap the buffer pushes land in before
+/// reading it out, rather than resetting indices a concurrent `push` might
+/// still be writing through. A push that already committed to the
+/// about-to-be-drained buffer before the swap is tracked via `in_flight`,
+/// and `drain` spins until it finishes — a short wait, since the critical
+/// section in `push` is just an index bump and a slot write.
+struct SampleRing {
+    buffers: [UnsafeCell<[RawSample; RING_CAPACITY]>; 2],
+    write_index: [AtomicUsize; 2],
+    in_flight: [AtomicUsize; 2],
+    active: AtomicUsize,
+}
+
+/// Safety: every field is either an atomic or a `[RawSample; N]` buffer
+/// accessed only through the disjoint-slot/quiescence protocol documented
+/// on `SampleRing` above, which is what makes concurrent `push`/`drain`
+/// from different threads sound despite the `UnsafeCell`s.
+unsafe impl Sync for SampleRing {}
+
+impl SampleRing {
+    const fn new() -> Self {
+        Self {
+            buffers: [
+                UnsafeCell::new([RawSample::EMPTY; RING_CAPACITY]),
+                UnsafeCell::new([RawSample::EMPTY; RING_CAPACITY]),
+            ],
+            write_index: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            in_flight: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            active: AtomicUsize::new(0),
+        }
+    }
+
+    /// Called only from the `SIGPROF` handler, potentially on any thread:
+    /// writes one sample into the next slot of whichever buffer is
+    /// currently active. Once that buffer is full, samples are dropped
+    /// until the next `drain`.
+    fn push(&self, sample: RawSample) {
+        // Register as in flight on the buffer read as active, then confirm
+        // it is still active, so a `drain` that swapped in between cannot
+        // miss this push in its `in_flight` wait.
+        let active = loop {
+            let active = self.active.load(Ordering::SeqCst);
+            self.in_flight[active].fetch_add(1, Ordering::SeqCst);
+            if self.active.load(Ordering::SeqCst) == active {
+                break active;
+            }
+            self.in_flight[active].fetch_sub(1, Ordering::SeqCst);
+        };
+
+        let idx = self.write_index[active].fetch_add(1, Ordering::AcqRel);
+        if idx < RING_CAPACITY {
+            unsafe {
+                (*self.buffers[active].get())[idx] = sample;
+            }
+        }
+
+        self.in_flight[active].fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// Drains every sample captured since the last call.
+    fn drain(&self) -> Vec<RawSample> {
+        let draining = self.active.fetch_xor(1, Ordering::SeqCst);
+
+        // Wait out any push that had already read `draining` as the active
+        // buffer before the swap above.
+        while self.in_flight[draining].load(Ordering::SeqCst) != 0 {
+            std::hint::spin_loop();
+        }
+
+        let len = self.write_index[draining]
+            .swap(0, Ordering::AcqRel)
+            .min(RING_CAPACITY);
+
+        let mut out = Vec::with_capacity(len);
+        unsafe {
+            let buffer = &*self.buffers[draining].get();
+            out.extend_from_slice(&buffer[..len]);
+        }
+
+        out
+    }
+}
+
+static RING: SampleRing = SampleRing::new();
+
+/// Copies `name_obj`'s text directly out of CPython's `PyASCIIObject`
+/// layout, into `out`, without calling into the CPython API.
+///
+/// `co_name` is always an identifier; in the overwhelmingly common ASCII
+/// case CPython stores it "compact": the struct fields below are followed
+/// immediately in memory by `length` bytes of string data, no indirection.
+/// Reading that directly (no `Py_IncRef`, no `PyUnicode_*` call) is what
+/// makes this safe to do from a signal handler — `ffi::Py_IncRef` is a
+/// plain, non-atomic `ob_refcnt += 1`, and a `SIGPROF` landing mid-update
+/// of that counter on the very thread doing the increment (the thread the
+/// GIL is frozen on) can lose the increment, leading to a premature free.
+/// Rarer non-ASCII-compact identifiers (legal in Python 3) are reported as
+/// unknown rather than decoded, since that requires parsing the
+/// corresponding UCS1/2/4 layouts.
+unsafe fn copy_frame_name(name_obj: *mut ffi::PyObject, out: &mut FrameName) {
+    #[repr(C)]
+    struct PyAsciiObject {
+        ob_refcnt: ffi::Py_ssize_t,
+        ob_type: *mut ffi::PyTypeObject,
+        length: ffi::Py_ssize_t,
+        hash: ffi::Py_hash_t,
+        state: u32,
+        wstr: *mut std::os::raw::c_void,
+    }
+
+    const COMPACT_BIT: u32 = 1 << 5;
+    const ASCII_BIT: u32 = 1 << 6;
+
+    let ascii = name_obj as *const PyAsciiObject;
+    let state = (*ascii).state;
+    if state & COMPACT_BIT == 0 || state & ASCII_BIT == 0 {
+        *out = FrameName::EMPTY;
+        return;
+    }
+
+    let length = (*ascii).length.max(0) as usize;
+    let data = (ascii as *const u8).add(std::mem::size_of::<PyAsciiObject>());
+    let copy_len = length.min(MAX_NAME_LEN);
+
+    out.len = copy_len as u8;
+    ptr::copy_nonoverlapping(data, out.bytes.as_mut_ptr(), copy_len);
+}
+
+/// `SIGPROF` handler: walks the current thread's Python frame chain
+/// (`PyThreadState` -> `frame` -> `f_back`), copying each frame's name
+/// into a preallocated [`RawSample`] and pushing it onto the shared
+/// [`RING`]. Touches only preallocated buffers, atomics, and raw reads of
+/// the (frozen, since this thread is what the signal interrupted) frame
+/// chain — no allocation, no hashing, and no CPython API call other than
+/// `PyThreadState_Get` to find the thread state.
+extern "C" fn sigprof_handler(_signal: i32) {
+    let mut sample = RawSample::EMPTY;
+
+    unsafe {
+        let tstate = ffi::PyThreadState_Get();
+        if tstate.is_null() {
+            return;
+        }
+
+        let mut frame = (*tstate).frame;
+        while !frame.is_null() && sample.len < MAX_STACK_DEPTH {
+            let code = (*frame).f_code;
+            copy_frame_name((*code).co_name, &mut sample.frames[sample.len]);
+            sample.len += 1;
+            frame = (*frame).f_back;
+        }
+    }
+
+    RING.push(sample);
+}
+
+/// Installs the `SIGPROF` handler and arms the process's profiling timer
+/// (`ITIMER_PROF`, which counts the CPU time the process uses) to fire it
+/// `frequency_hz` times per second of that time.
+fn install_timer(frequency_hz: u32) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = sigprof_handler as usize;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGPROF, &action, ptr::null_mut());
+
+        let interval_us = 1_000_000 / frequency_hz.max(1) as i64;
+        let interval = libc::timeval {
+            tv_sec: interval_us / 1_000_000,
+            tv_usec: interval_us % 1_000_000,
+        };
+        let timer = libc::itimerval {
+            it_interval: interval,
+            it_value: interval,
+        };
+        libc::setitimer(libc::ITIMER_PROF, &timer, ptr::null_mut());
+    }
+}
+
+/// Disarms the sampling timer and restores the default `SIGPROF` handler.
+fn uninstall_timer() {
+    unsafe {
+        let disarmed: libc::itimerval = std::mem::zeroed();
+        libc::setitimer(libc::ITIMER_PROF, &disarmed, ptr::null_mut());
+        libc::signal(libc::SIGPROF, libc::SIG_DFL);
+    }
+}
+
+/// Statistical sampling mode, driven by a `SIGPROF` interval timer instead
+/// of `PyEval_SetProfile`'s deterministic per-call hook (pprof-style).
+///
+/// Samples are aggregated into a self (innermost frame) and an inclusive
+/// (every distinct frame on the sampled stack) hit-count map, convertible
+/// to estimated time via the sampling frequency.
+pub struct SamplingProfiler {
+    frequency_hz: u32,
+    interner: StringInterner,
+}
+
+impl SamplingProfiler {
+    pub fn new(frequency_hz: u32) -> Self {
+        Self {
+            frequency_hz,
+            interner: StringInterner::new(),
+        }
+    }
+
+    /// Estimated nanoseconds represented by a single sample hit.
+    fn ns_per_sample(&self) -> u128 {
+        1_000_000_000u128 / u128::from(self.frequency_hz.max(1))
+    }
+}
+
+impl Lifecycle for SamplingProfiler {
+    fn enable(&self) {
+        install_timer(self.frequency_hz);
+    }
+
+    fn disable(&self) {
+        uninstall_timer();
+    }
+}
+
+impl AbstractProfiler for SamplingProfiler {
+    // Sampling doesn't hook individual calls/returns; all the work happens
+    // in `sigprof_handler` and `get_statistics`.
+    fn update(&mut self) {}
+    fn on_call(&mut self, _name: &str) {}
+    fn on_return(&mut self, _name: &str) {}
+    fn on_c_call(&mut self, _name: &str) {}
+    fn on_c_return(&mut self, _name: &str) {}
+
+    fn get_statistics(&mut self) -> Vec<FunctionStatistics> {
+        let samples = RING.drain();
+        let ns_per_sample = self.ns_per_sample();
+
+        // (self_hits, inclusive_hits) per symbol
+        let mut hits: std::collections::HashMap<Symbol, (usize, usize)> = std::collections::HashMap::new();
+
+        for sample in samples {
+            let mut seen = HashSet::new();
+
+            for i in 0..sample.len {
+                let name = match sample.frames[i].as_str() {
+                    Some(name) => name,
+                    // Non-ASCII-compact identifier (see `copy_frame_name`)
+                    // or truncated garbage; not worth attributing.
+                    None => continue,
+                };
+                let symbol = self.interner.get_or_intern(name);
+
+                let entry = hits.entry(symbol).or_insert((0, 0));
+                if i == 0 {
+                    entry.0 += 1;
+                }
+                if seen.insert(symbol) {
+                    entry.1 += 1;
+                }
+            }
+        }
+
+        hits.into_iter()
+            .map(|(symbol, (self_hits, inclusive_hits))| {
+                let name = self.interner.resolve(symbol).unwrap().to_owned();
+                FunctionStatistics {
+                    name,
+                    num_calls: self_hits,
+                    total: self_hits as u128 * ns_per_sample,
+                    cumulative: inclusive_hits as u128 * ns_per_sample,
+                }
+            })
+            .collect()
+    }
+
+    fn get_call_tree(&mut self) -> Vec<CallTreeStats> {
+        // Sampling reconstructs stacks per-sample rather than threading a
+        // persistent call-tree arena; callers wanting hierarchical data
+        // should use the deterministic `Profiler` instead.
+        Vec::new()
+    }
+
+    fn dump_callgrind(&self, _path: &str) -> io::Result<()> {
+        // Same limitation as `get_call_tree`: there's no persistent
+        // caller/callee arena to export edges from in sampling mode.
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Callgrind export requires the deterministic profiling mode",
+        ))
+    }
+}
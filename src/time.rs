@@ -23,6 +23,8 @@ impl Counter for TimeCounter {
     type DifferenceType = Duration;
     type ValueType = Instant;
 
+    const UNIT: &'static str = "Nanoseconds";
+
     fn read(&self) -> Self::ValueType {
         Instant::now()
     }
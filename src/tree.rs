@@ -0,0 +1,57 @@
+use crate::{counter::Counter, profiler::Symbol, stopwatch::Statistics};
+
+pub(crate) type NodeId = usize;
+
+/// A single frame in the hierarchical call tree.
+///
+/// The same [`Symbol`] can appear as several distinct nodes when it is
+/// reached through different call paths, so callers and callees stay
+/// distinguishable (e.g. `parse` called from `main` vs. from `tokenize`).
+pub(crate) struct CallTreeNode<C: Counter> {
+    pub symbol: Symbol,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub stats: Option<Statistics<C>>,
+}
+
+/// Arena holding every call-tree node recorded so far.
+///
+/// Nodes are only ever appended, so existing [`NodeId`]s stay valid for the
+/// lifetime of the tree.
+pub(crate) struct CallTree<C: Counter> {
+    nodes: Vec<CallTreeNode<C>>,
+}
+
+impl<C: Counter> CallTree<C> {
+    pub fn new() -> Self {
+        Self { nodes: Vec::new() }
+    }
+
+    /// Pushes a new child node under `parent` (or a root, if `None`) and
+    /// returns its id.
+    pub fn push_child(&mut self, parent: Option<NodeId>, symbol: Symbol) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(CallTreeNode {
+            symbol,
+            parent,
+            children: Vec::new(),
+            stats: None,
+        });
+
+        if let Some(parent) = parent {
+            self.nodes[parent].children.push(id);
+        }
+
+        id
+    }
+
+    /// Finalizes the `total`/`cumulative` statistics for `node`, leaving it
+    /// attached to its parent.
+    pub fn finish(&mut self, node: NodeId, stats: Statistics<C>) {
+        self.nodes[node].stats = Some(stats);
+    }
+
+    pub fn nodes(&self) -> &[CallTreeNode<C>] {
+        &self.nodes
+    }
+}